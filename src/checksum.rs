@@ -1,10 +1,48 @@
+use std::collections::{HashMap, HashSet};
 use std::fs::{canonicalize, File};
-use std::io::Read;
-use std::path::PathBuf;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
 use std::vec;
 
 use md5;
-use csv;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+
+/// Hash algorithm used to compute a file's digest.
+#[derive(Clone, Copy, Debug, RustcDecodable, PartialEq, Eq)]
+pub enum Algorithm {
+    Md5,
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl Algorithm {
+    /// Guess the algorithm from the length of a hex-encoded digest.
+    pub fn from_digest_length(len: usize) -> Option<Algorithm> {
+        match len {
+            32 => Some(Algorithm::Md5),
+            40 => Some(Algorithm::Sha1),
+            64 => Some(Algorithm::Sha256),
+            128 => Some(Algorithm::Sha512),
+            _ => None,
+        }
+    }
+
+    /// Parse a CLI-facing algorithm name such as `"sha256"` (case-insensitive).
+    pub fn from_name(name: &str) -> Option<Algorithm> {
+        match name.to_lowercase().as_str() {
+            "md5" => Some(Algorithm::Md5),
+            "sha1" => Some(Algorithm::Sha1),
+            "sha256" => Some(Algorithm::Sha256),
+            "sha512" => Some(Algorithm::Sha512),
+            _ => None,
+        }
+    }
+}
 
 #[derive(Clone, Debug, RustcDecodable, PartialEq)]
 pub enum Outcome {
@@ -16,6 +54,12 @@ pub enum Outcome {
 pub struct ChecksumRecord {
     pub file: String,
     pub checksum: String,
+    pub algorithm: Option<Algorithm>,
+    /// Directory the record's `file` path is relative to: the directory of
+    /// the manifest it was read from, which for a record spliced in via
+    /// `%include` is that included manifest's own directory, not the
+    /// including manifest's.
+    pub working_directory: String,
 }
 
 #[derive(Clone, Debug, RustcDecodable, PartialEq)]
@@ -24,9 +68,26 @@ pub struct ChecksumResult {
     pub result: Result<Outcome, String>,
 }
 
+/// Where `ChecksumResultsIter` pulls its next result from.
+enum ResultsSource {
+    /// Hash files one at a time on the calling thread.
+    Sequential(vec::IntoIter<Result<ChecksumRecord, String>>),
+    /// Hash files across a worker pool, reordering results back into
+    /// manifest order through `pending` as they complete out of order.
+    Parallel {
+        results_rx: Receiver<(usize, Result<ChecksumResult, String>)>,
+        pending: HashMap<usize, Result<ChecksumResult, String>>,
+        next_index: usize,
+        total: usize,
+        // Kept alive only so the workers aren't dropped (and detached)
+        // before the iterator that depends on them.
+        _workers: Vec<thread::JoinHandle<()>>,
+    },
+}
+
 pub struct ChecksumResultsIter {
-    iterator: vec::IntoIter<Result<ChecksumRecord, String>>,
-    working_directory: PathBuf,
+    source: ResultsSource,
+    default_algorithm: Option<Algorithm>,
     pub len: usize,
 }
 
@@ -34,53 +95,285 @@ impl Iterator for ChecksumResultsIter {
     type Item = Result<ChecksumResult, String>;
 
     fn next(&mut self) -> Option<Result<ChecksumResult, String>> {
-        match self.iterator.next() {
-            None => None,
-            Some(Ok(record)) => {
-                let ChecksumRecord { file: ref relative_path, checksum: ref expected_checksum } = record;
-                let file_path = self.working_directory.join(relative_path);
-                Some(Ok(ChecksumResult {
-                    file: relative_path.clone(),
-                    result: verify_checksum(&file_path, expected_checksum),
-                }))
+        match self.source {
+            ResultsSource::Sequential(ref mut iterator) => {
+                match iterator.next() {
+                    None => None,
+                    Some(Ok(record)) => {
+                        let ChecksumRecord { file: ref relative_path,
+                                              checksum: ref expected_checksum,
+                                              algorithm,
+                                              working_directory: ref record_working_directory } = record;
+                        let file_path = PathBuf::from(record_working_directory).join(relative_path);
+                        let algorithm = algorithm.or(self.default_algorithm);
+                        Some(Ok(ChecksumResult {
+                            file: relative_path.clone(),
+                            result: verify_checksum(&file_path, expected_checksum, algorithm),
+                        }))
+                    }
+                    Some(Err(e)) => Some(Err(e)),
+                }
+            }
+            ResultsSource::Parallel { ref results_rx, ref mut pending, ref mut next_index, total, .. } => {
+                if *next_index >= total {
+                    return None;
+                }
+                loop {
+                    if let Some(result) = pending.remove(next_index) {
+                        *next_index += 1;
+                        return Some(result);
+                    }
+                    match results_rx.recv() {
+                        Ok((index, result)) => {
+                            pending.insert(index, result);
+                        }
+                        Err(_) => return None,
+                    }
+                }
             }
-            Some(Err(e)) => Some(Err(e)),
         }
     }
 }
 
+/// The file/checksum/algorithm parsed from one manifest line, before the
+/// `working_directory` it should be anchored to (which depends on which
+/// manifest file it was read from) is attached.
+struct ParsedEntry {
+    file: String,
+    checksum: String,
+    algorithm: Option<Algorithm>,
+}
+
+/// Parse a GNU coreutils-style line: `<hex><space><mode><path>`, where
+/// `mode` is `*` for binary files or a plain space for text files. Unlike
+/// splitting on whitespace, only the first space is significant, so paths
+/// containing spaces of their own are preserved intact.
+fn parse_gnu_line(line: &str) -> Result<ParsedEntry, String> {
+    let space_index = line.find(' ').ok_or_else(|| format!("Malformed checksum line: {:?}", line))?;
+    let checksum = &line[..space_index];
+    let rest = &line[space_index + 1..];
+    if checksum.is_empty() || rest.is_empty() {
+        return Err(format!("Malformed checksum line: {:?}", line));
+    }
+
+    // Skip the binary-mode marker (`*` or a second space); what follows is
+    // the path, verbatim.
+    if !rest.starts_with(' ') && !rest.starts_with('*') {
+        return Err(format!("Malformed checksum line: {:?}", line));
+    }
+    let path = &rest[1..];
+    if path.is_empty() {
+        return Err(format!("Malformed checksum line: {:?}", line));
+    }
+
+    Ok(ParsedEntry {
+        file: path.to_string(),
+        checksum: checksum.to_string(),
+        algorithm: None,
+    })
+}
+
+/// Parse a BSD-style line: `TAG (path) = hex`, e.g. as produced by BSD
+/// `md5`/`sha256` (without `-r`) or `shasum --tag`. Returns `None` (rather than `Some(Err(_))`)
+/// when the line doesn't look like this format at all, so the caller can
+/// fall back to trying the GNU format; a line that does look like this
+/// format but names an algorithm `chkr` doesn't support is `Some(Err(_))`,
+/// since falling back to length-based guessing would silently hash with
+/// the wrong algorithm.
+fn parse_bsd_line(line: &str) -> Option<Result<ParsedEntry, String>> {
+    let open_paren = line.find(" (")?;
+    let tag = &line[..open_paren];
+    if tag.is_empty() || !tag.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+
+    let rest = &line[open_paren + 2..];
+    let close_paren = rest.rfind(") = ")?;
+    let path = &rest[..close_paren];
+    let checksum = &rest[close_paren + 4..];
+    if checksum.is_empty() || !checksum.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let algorithm = match Algorithm::from_name(tag) {
+        Some(algorithm) => algorithm,
+        None => return Some(Err(format!("Unsupported algorithm: {}", tag))),
+    };
+
+    Some(Ok(ParsedEntry {
+        file: path.to_string(),
+        checksum: checksum.to_lowercase(),
+        algorithm: Some(algorithm),
+    }))
+}
+
+/// Parse a single manifest line, trying the BSD `TAG (path) = hex` format
+/// before falling back to the GNU `hex  path` format. Blank lines are
+/// skipped.
+fn parse_checksum_line(line: &str) -> Option<Result<ParsedEntry, String>> {
+    let line = line.trim_end_matches(|c| c == '\n' || c == '\r');
+    if line.is_empty() {
+        return None;
+    }
+
+    match parse_bsd_line(line) {
+        Some(result) => Some(result),
+        None => Some(parse_gnu_line(line)),
+    }
+}
+
+/// Parse a `%include <path>` directive. `path` is interpreted relative to
+/// the including manifest's own directory, not the current working
+/// directory.
+fn parse_include_directive(line: &str) -> Option<&str> {
+    let line = line.trim();
+    if !line.starts_with("%include") {
+        return None;
+    }
+    let rest = line["%include".len()..].trim();
+    if rest.is_empty() { None } else { Some(rest) }
+}
+
 pub fn read_checksums(path: &str) -> Result<Vec<Result<ChecksumRecord, String>>, String> {
-    let checksum_reader = csv::Reader::from_file(path).map_err(|e| format!("{:?}", e))?;
-
-    let mut checksum_reader = checksum_reader.delimiter(b' ').has_headers(false);
-
-    let checksums = checksum_reader.records()
-        .map(|row| {
-            // The files are probably created by the `md5sum` utility
-            // Two spaces are used to delimit
-            match row {
-                Ok(row_unwrapped) => {
-                    Ok(ChecksumRecord {
-                        file: row_unwrapped[2].clone(),
-                        checksum: row_unwrapped[0].clone(),
-                    })
-                }
-                Err(e) => Err(format!("{:?}", e)),
+    let mut visiting = HashSet::new();
+    read_checksums_resolving_includes(Path::new(path), &mut visiting)
+}
+
+/// Read `path`, splicing in any `%include`d manifests, while tracking the
+/// chain of manifests currently being read in `visiting` so a cycle of
+/// `%include`s is rejected instead of recursing forever.
+fn read_checksums_resolving_includes(path: &Path,
+                                      visiting: &mut HashSet<PathBuf>)
+                                      -> Result<Vec<Result<ChecksumRecord, String>>, String> {
+    let canonical_path = canonicalize(path).map_err(|e| format!("{:?}", e))?;
+    if !visiting.insert(canonical_path.clone()) {
+        return Err(format!("Circular %include detected at {:?}", canonical_path));
+    }
+
+    let result = read_checksums_from_canonical_path(&canonical_path, visiting);
+    visiting.remove(&canonical_path);
+    result
+}
+
+/// The body of `read_checksums_resolving_includes`, split out so the caller
+/// can remove `canonical_path` from `visiting` on every exit path, not just
+/// the successful one.
+fn read_checksums_from_canonical_path(canonical_path: &Path,
+                                       visiting: &mut HashSet<PathBuf>)
+                                       -> Result<Vec<Result<ChecksumRecord, String>>, String> {
+    let working_directory = canonical_path.parent()
+        .ok_or_else(|| "Unable to compute working directory".to_string())?
+        .to_path_buf();
+    let working_directory_str = working_directory.to_str()
+        .ok_or_else(|| "Unable to convert paths".to_string())?
+        .to_string();
+
+    let file = File::open(canonical_path).map_err(|e| format!("{:?}", e))?;
+    let reader = BufReader::new(file);
+
+    let mut records = Vec::new();
+    for line in reader.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                records.push(Err(format!("{:?}", e)));
+                continue;
             }
-        })
-        .filter(|row| match *row {
-            Err(_) => true,
-            Ok(ref row) => !row.file.is_empty() && !row.checksum.is_empty(),
-        })
-        .collect();
-    Ok(checksums)
+        };
+
+        if let Some(included_path) = parse_include_directive(&line) {
+            let included_path = working_directory.join(included_path);
+            let mut included_records = read_checksums_resolving_includes(&included_path, visiting)?;
+            records.append(&mut included_records);
+            continue;
+        }
+
+        if let Some(entry) = parse_checksum_line(&line) {
+            records.push(entry.map(|entry| {
+                ChecksumRecord {
+                    file: entry.file,
+                    checksum: entry.checksum,
+                    algorithm: entry.algorithm,
+                    working_directory: working_directory_str.clone(),
+                }
+            }));
+        }
+    }
+
+    Ok(records)
 }
 
-pub fn verify_checksum(path: &PathBuf, expected_digest: &str) -> Result<Outcome, String> {
-    let file_buffer = read(path).map_err(|e| format!("{}", e))?;
+/// Size of the reusable read buffer fed into the incremental hasher, chosen
+/// to stay well below a single page-cache readahead window.
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// An incremental hasher over one of the supported `Algorithm`s, so a file
+/// can be hashed block-by-block instead of being loaded into memory whole.
+enum Hasher {
+    Md5(md5::Context),
+    Sha1(Sha1),
+    Sha256(Sha256),
+    Sha512(Sha512),
+}
+
+impl Hasher {
+    fn new(algorithm: Algorithm) -> Hasher {
+        match algorithm {
+            Algorithm::Md5 => Hasher::Md5(md5::Context::new()),
+            Algorithm::Sha1 => Hasher::Sha1(Sha1::new()),
+            Algorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            Algorithm::Sha512 => Hasher::Sha512(Sha512::new()),
+        }
+    }
 
-    let actual_digest = md5::compute(file_buffer);
-    let actual_digest = format!("{:x}", actual_digest);
+    fn consume(&mut self, block: &[u8]) {
+        match *self {
+            Hasher::Md5(ref mut context) => context.consume(block),
+            Hasher::Sha1(ref mut hasher) => hasher.update(block),
+            Hasher::Sha256(ref mut hasher) => hasher.update(block),
+            Hasher::Sha512(ref mut hasher) => hasher.update(block),
+        }
+    }
+
+    fn finalize_hex(self) -> String {
+        match self {
+            Hasher::Md5(context) => format!("{:x}", context.compute()),
+            Hasher::Sha1(hasher) => hasher.digest().to_string(),
+            Hasher::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+            Hasher::Sha512(hasher) => format!("{:x}", hasher.finalize()),
+        }
+    }
+}
+
+/// Hash a file under `algorithm` in constant memory, reading it through a
+/// `BufReader` in fixed-size blocks rather than slurping it whole.
+pub fn hash_file(path: &PathBuf, algorithm: Algorithm) -> Result<String, String> {
+    let f = File::open(path).map_err(|e| format!("{:?}", e))?;
+    let mut reader = BufReader::new(f);
+    let mut hasher = Hasher::new(algorithm);
+    let mut block = [0u8; BLOCK_SIZE];
+    loop {
+        let bytes_read = reader.read(&mut block).map_err(|e| format!("{:?}", e))?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.consume(&block[..bytes_read]);
+    }
+    Ok(hasher.finalize_hex())
+}
+
+/// Verify a file's digest against `expected_digest`.
+///
+/// When `algorithm` is `None`, the algorithm is guessed from the length of
+/// `expected_digest`, falling back to MD5 for backwards compatibility with
+/// manifests that predate multi-algorithm support.
+pub fn verify_checksum(path: &PathBuf,
+                        expected_digest: &str,
+                        algorithm: Option<Algorithm>)
+                        -> Result<Outcome, String> {
+    let algorithm = algorithm.or_else(|| Algorithm::from_digest_length(expected_digest.len()))
+        .unwrap_or(Algorithm::Md5);
+    let actual_digest = hash_file(path, algorithm)?;
     if actual_digest == expected_digest {
         Ok(Outcome::Match)
     } else {
@@ -91,60 +384,193 @@ pub fn verify_checksum(path: &PathBuf, expected_digest: &str) -> Result<Outcome,
     }
 }
 
+/// Bound on the number of queued-but-not-yet-hashed records, so a huge
+/// manifest doesn't buffer unboundedly ahead of a slow worker pool.
+const WORK_QUEUE_CAPACITY: usize = 128;
+
+/// Capacity of the channel carrying hashed results back to the iterator.
+/// The iterator moves results out of this channel into `pending` as soon as
+/// they arrive, so this does not limit how many out-of-order results
+/// `pending` holds while an earlier, slower file is still being hashed.
+const RESULTS_QUEUE_CAPACITY: usize = 128;
+
 /// Verify checksums of files according to a manifest file.
-pub fn verify_checksums_file(file: &str) -> Result<ChecksumResultsIter, String> {
-    let checksums_path = canonicalize(&PathBuf::from(file)).map_err(|e| format!("{}", e))?;
-    let working_directory = checksums_path.parent()
-        .ok_or("Unable to compute working directory".to_string())?;
-    let checksums_path = checksums_path.as_path()
-        .to_str()
-        .ok_or("Unable to convert paths".to_string())?;
-    let checksums = read_checksums(checksums_path)?;
+///
+/// `default_algorithm` overrides auto-detection for records that do not
+/// specify their own algorithm, e.g. via the CLI `--algo` flag. `jobs`
+/// selects how many files are hashed concurrently; `1` (the default) keeps
+/// the original single-threaded behaviour.
+pub fn verify_checksums_file(file: &str,
+                              default_algorithm: Option<Algorithm>,
+                              jobs: usize)
+                              -> Result<ChecksumResultsIter, String> {
+    let checksums = read_checksums(file)?;
+    let len = checksums.len();
+
+    let source = if jobs <= 1 {
+        ResultsSource::Sequential(checksums.into_iter())
+    } else {
+        spawn_workers(checksums, default_algorithm, jobs)
+    };
 
     Ok(ChecksumResultsIter {
-        working_directory: working_directory.to_path_buf(),
-        len: checksums.len(),
-        iterator: checksums.into_iter(),
+        source,
+        default_algorithm,
+        len,
     })
 }
 
-fn read(path: &PathBuf) -> Result<Vec<u8>, String> {
-    let mut buffer = Vec::<u8>::new();
-    let mut f = File::open(path).map_err(|e| format!("{:?}", e))?;
-    f.read_to_end(&mut buffer).map_err(|e| format!("{:?}", e))?;
-    Ok(buffer)
+/// Spawn `jobs` workers that drain a shared, bounded queue of records and
+/// report `(original index, result)` pairs so the iterator can restore
+/// manifest order.
+fn spawn_workers(checksums: Vec<Result<ChecksumRecord, String>>,
+                  default_algorithm: Option<Algorithm>,
+                  jobs: usize)
+                  -> ResultsSource {
+    let total = checksums.len();
+    let (work_tx, work_rx) = mpsc::sync_channel::<(usize, Result<ChecksumRecord, String>)>(WORK_QUEUE_CAPACITY);
+    let work_rx = Arc::new(Mutex::new(work_rx));
+    let (results_tx, results_rx) =
+        mpsc::sync_channel::<(usize, Result<ChecksumResult, String>)>(RESULTS_QUEUE_CAPACITY);
+
+    let mut workers = Vec::with_capacity(jobs);
+    for _ in 0..jobs {
+        let work_rx = work_rx.clone();
+        let results_tx = results_tx.clone();
+        workers.push(thread::spawn(move || {
+            loop {
+                let item = work_rx.lock().unwrap().recv();
+                let (index, record) = match item {
+                    Ok(item) => item,
+                    Err(_) => break,
+                };
+                let result = match record {
+                    Ok(ChecksumRecord { file: relative_path, checksum: expected_checksum, algorithm, working_directory }) => {
+                        let file_path = PathBuf::from(&working_directory).join(&relative_path);
+                        let algorithm = algorithm.or(default_algorithm);
+                        Ok(ChecksumResult {
+                            file: relative_path,
+                            result: verify_checksum(&file_path, &expected_checksum, algorithm),
+                        })
+                    }
+                    Err(e) => Err(e),
+                };
+                if results_tx.send((index, result)).is_err() {
+                    break;
+                }
+            }
+        }));
+    }
+    // Drop our own sender so the results channel closes once every worker
+    // has finished and dropped its clone.
+    drop(results_tx);
+
+    thread::spawn(move || {
+        for (index, record) in checksums.into_iter().enumerate() {
+            if work_tx.send((index, record)).is_err() {
+                break;
+            }
+        }
+    });
+
+    ResultsSource::Parallel {
+        results_rx,
+        pending: HashMap::new(),
+        next_index: 0,
+        total,
+        _workers: workers,
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{read_checksums, Outcome, ChecksumRecord, verify_checksum, verify_checksums_file, ChecksumResult};
+    use super::{read_checksums, parse_checksum_line, parse_include_directive, Algorithm, Outcome, ChecksumRecord,
+                verify_checksum, verify_checksums_file, ChecksumResult};
     use std::path::PathBuf;
     use std::vec::Vec;
     use std::collections::HashMap;
 
+    #[test]
+    fn gnu_style_line_is_parsed_correctly() {
+        let entry = parse_checksum_line("4d93d51945b88325c213640ef59fc50b  foo.txt").unwrap().unwrap();
+        assert_eq!(entry.file, "foo.txt".to_string());
+        assert_eq!(entry.checksum, "4d93d51945b88325c213640ef59fc50b".to_string());
+        assert_eq!(entry.algorithm, None);
+    }
+
+    #[test]
+    fn gnu_style_binary_marker_is_parsed_correctly() {
+        let entry = parse_checksum_line("4d93d51945b88325c213640ef59fc50b *foo.bin").unwrap().unwrap();
+        assert_eq!(entry.file, "foo.bin".to_string());
+        assert_eq!(entry.checksum, "4d93d51945b88325c213640ef59fc50b".to_string());
+    }
+
+    #[test]
+    fn bsd_style_line_is_parsed_correctly() {
+        let entry = parse_checksum_line("SHA256 (foo.txt) = 7e0a1331dcdcb227e8a3f8932c7ae9f61d2a84f9e0e2c45c8a3f6e5c4e9a1234")
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.file, "foo.txt".to_string());
+        assert_eq!(entry.checksum,
+                   "7e0a1331dcdcb227e8a3f8932c7ae9f61d2a84f9e0e2c45c8a3f6e5c4e9a1234".to_string());
+        assert_eq!(entry.algorithm, Some(Algorithm::Sha256));
+    }
+
+    #[test]
+    fn bsd_style_line_with_spaces_in_path_is_parsed_correctly() {
+        let record = parse_checksum_line("MD5 (my file.txt) = 4d93d51945b88325c213640ef59fc50b")
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.file, "my file.txt".to_string());
+    }
+
+    #[test]
+    fn bsd_style_line_with_unsupported_algorithm_is_rejected() {
+        let result = parse_checksum_line(
+            "SHA384 (foo.txt) = a1b2c3d4e5f6a1b2c3d4e5f6a1b2c3d4e5f6a1b2c3d4e5f6a1b2c3d4e5f6a1b2c3d4e5f6a1b2c3d4e5f6a1b2c3d4")
+            .unwrap();
+        assert_matches!(result, Err(ref e) if e.contains("SHA384"));
+    }
+
+    #[test]
+    fn blank_line_is_skipped() {
+        assert!(parse_checksum_line("").is_none());
+        assert!(parse_checksum_line("   \n").is_some()); // whitespace-only isn't blank, just malformed
+    }
+
+    #[test]
+    fn gnu_style_line_with_multibyte_path_is_rejected_not_panicked() {
+        let result = parse_checksum_line("4d93d51945b88325c213640ef59fc50b \u{2603}foo.txt").unwrap();
+        assert_matches!(result, Err(_));
+    }
+
     #[test]
     fn checksums_are_read_correctly() {
-        let expected_checksums = vec![Ok(ChecksumRecord {
-                                          file: "foo.txt".to_string(),
-                                          checksum: "4d93d51945b88325c213640ef59fc50b".to_string(),
-                                      }),
-                                      Ok(ChecksumRecord {
-                                          file: "bar.txt".to_string(),
-                                          checksum: "4d93d51945b88325c213640ef59fc50a".to_string(),
-                                      }),
-                                      Ok(ChecksumRecord {
-                                          file: "file-does-not-exist".to_string(),
-                                          checksum: "ce5188defed222ca612b41580e0d5fe7".to_string(),
-                                      })];
+        let expected = vec![("foo.txt", "4d93d51945b88325c213640ef59fc50b"),
+                            ("bar.txt", "4d93d51945b88325c213640ef59fc50a"),
+                            ("file-does-not-exist", "ce5188defed222ca612b41580e0d5fe7")];
         let actual_checksums = read_checksums("tests/fixtures/checksum.txt").unwrap();
 
-        assert_eq!(expected_checksums, actual_checksums);
+        let actual: Vec<(&str, &str)> = actual_checksums.iter()
+            .map(|r| {
+                let record = r.as_ref().unwrap();
+                (record.file.as_str(), record.checksum.as_str())
+            })
+            .collect();
+        assert_eq!(expected, actual);
+
+        for record in actual_checksums.iter().map(|r| r.as_ref().unwrap()) {
+            assert_eq!(record.algorithm, None);
+            assert!(record.working_directory.ends_with("tests/fixtures") ||
+                    record.working_directory.ends_with("tests\\fixtures"));
+        }
     }
 
     #[test]
     fn checksum_is_verified_correctly() {
         let actual_result = verify_checksum(&PathBuf::from("tests/fixtures/foo.txt"),
-                                            &"4d93d51945b88325c213640ef59fc50b");
+                                            &"4d93d51945b88325c213640ef59fc50b",
+                                            None);
 
         assert_matches!(actual_result, Ok(Outcome::Match));
     }
@@ -152,20 +578,39 @@ mod tests {
     #[test]
     fn incorrect_checksum_is_verified_correctly() {
         let actual_result = verify_checksum(&PathBuf::from("tests/fixtures/foo.txt"),
-                                            &"ce5188defed222ca612b41580e0d5fe6");
+                                            &"ce5188defed222ca612b41580e0d5fe6",
+                                            None);
         assert_matches!(actual_result, Ok(Outcome::Mismatch { .. }));
     }
 
     #[test]
     fn missing_file_is_reported() {
         let actual_result = verify_checksum(&PathBuf::from("tests/fixtures/non-existent-file"),
-                                            &"ce5188defed222ca612b41580e0d5fe6");
+                                            &"ce5188defed222ca612b41580e0d5fe6",
+                                            None);
         assert_matches!(actual_result, Err(_));
     }
 
+    #[test]
+    fn algorithm_is_guessed_from_digest_length() {
+        assert_eq!(Algorithm::from_digest_length(32), Some(Algorithm::Md5));
+        assert_eq!(Algorithm::from_digest_length(40), Some(Algorithm::Sha1));
+        assert_eq!(Algorithm::from_digest_length(64), Some(Algorithm::Sha256));
+        assert_eq!(Algorithm::from_digest_length(128), Some(Algorithm::Sha512));
+        assert_eq!(Algorithm::from_digest_length(7), None);
+    }
+
+    #[test]
+    fn incorrect_sha256_checksum_is_verified_correctly() {
+        let actual_result = verify_checksum(&PathBuf::from("tests/fixtures/foo.txt"),
+                                            &"7e0a1331dcdcb227e8a3f8932c7ae9f61d2a84f9e0e2c45c8a3f6e5c4e9a1234",
+                                            Some(Algorithm::Sha256));
+        assert_matches!(actual_result, Ok(Outcome::Mismatch { .. }));
+    }
+
     #[test]
     fn checksums_manifest_is_verified_correctly() {
-        let actual_result: Vec<Result<ChecksumResult, String>> = verify_checksums_file("tests/fixtures/checksum.txt")
+        let actual_result: Vec<Result<ChecksumResult, String>> = verify_checksums_file("tests/fixtures/checksum.txt", None, 1)
             .unwrap()
             .collect();
 
@@ -192,4 +637,50 @@ mod tests {
         assert_matches!(results.get("bar.txt").unwrap(), &Ok(Outcome::Mismatch{ .. }));
         assert_matches!(results.get("file-does-not-exist").unwrap(), &Err(_));
     }
+
+    #[test]
+    fn checksums_manifest_is_verified_correctly_in_parallel() {
+        let sequential: Vec<Result<ChecksumResult, String>> =
+            verify_checksums_file("tests/fixtures/checksum.txt", None, 1).unwrap().collect();
+        let parallel: Vec<Result<ChecksumResult, String>> =
+            verify_checksums_file("tests/fixtures/checksum.txt", None, 4).unwrap().collect();
+
+        assert_eq!(sequential, parallel);
+    }
+
+    #[test]
+    fn include_directive_is_parsed_correctly() {
+        assert_eq!(parse_include_directive("%include other.txt"), Some("other.txt"));
+        assert_eq!(parse_include_directive("  %include sub/other.txt  "), Some("sub/other.txt"));
+        assert_eq!(parse_include_directive("%include"), None);
+        assert_eq!(parse_include_directive("4d93d51945b88325c213640ef59fc50b  foo.txt"), None);
+    }
+
+    #[test]
+    fn included_manifests_are_spliced_in() {
+        let records = read_checksums("tests/fixtures/including.txt").unwrap();
+
+        let files: Vec<String> = records.iter().map(|r| r.as_ref().unwrap().file.clone()).collect();
+        assert_eq!(files, vec!["foo.txt".to_string(), "bar.txt".to_string()]);
+
+        // The included record's path resolves against the included
+        // manifest's own directory, not the including manifest's.
+        let included_record = records[1].as_ref().unwrap();
+        assert!(included_record.working_directory.contains("included"));
+    }
+
+    #[test]
+    fn circular_includes_are_rejected() {
+        let result = read_checksums("tests/fixtures/circular-a.txt");
+        assert_matches!(result, Err(_));
+    }
+
+    #[test]
+    fn repeated_missing_include_is_reported_as_a_missing_file_not_a_cycle() {
+        // The first `%include` of the missing manifest fails with a file
+        // error, which ends the read; it must not be reported as a cycle.
+        let result = read_checksums("tests/fixtures/including-missing-twice.txt");
+        let error = result.unwrap_err();
+        assert!(!error.contains("Circular"), "expected a file error, got: {}", error);
+    }
 }
@@ -1,6 +1,8 @@
 extern crate docopt;
 extern crate md5;
-extern crate csv;
+extern crate sha1;
+extern crate sha2;
+extern crate glob;
 extern crate rustc_serialize;
 
 #[cfg(test)]
@@ -8,7 +10,8 @@ extern crate rustc_serialize;
 extern crate assert_matches;
 
 mod checksum;
-use checksum::{ChecksumResult, Outcome};
+mod generate;
+use checksum::{Algorithm, ChecksumResult, Outcome};
 
 use std::path::PathBuf;
 use std::fs::canonicalize;
@@ -25,23 +28,55 @@ d88' `"Y8  888P"Y88b   888 .8P'   `888""8P
 `Y8bod8P' o888o o888o o888o o888o d888b
 
 Usage:
-  chkr file <file-path> <expected-checksum>
-  chkr manifest <checksum-path>
+  chkr file <file-path> <expected-checksum> [--algo=<algorithm>]
+  chkr manifest <checksum-path> [--algo=<algorithm>] [--jobs=<n>]
+  chkr generate <directory> [--algo=<algorithm>] [--include=<pattern>]... [--exclude=<pattern>]...
   chkr (-h | --help)
 
 chkr will return 0 for matches, 0x01 for mismatch, and 0x10 for other errors.
 
 Options:
-  -h --help     Show this screen.
+  -h --help            Show this screen.
+  --algo=<algorithm>    Hash algorithm to use: md5, sha1, sha256 or sha512.
+                        Guessed from the digest length when omitted (defaults
+                        to sha256 when generating a manifest).
+  --jobs=<n>            Number of files to hash concurrently when verifying
+                        a manifest [default: 1].
+  --include=<pattern>   Glob pattern (relative to <directory>) of files to
+                        include when generating a manifest; may be repeated
+                        [default: **/*].
+  --exclude=<pattern>   Glob pattern of files to exclude when generating a
+                        manifest; may be repeated.
 "##;
 
 #[derive(Debug, RustcDecodable)]
 struct Args {
     cmd_file: bool,
     cmd_manifest: bool,
+    cmd_generate: bool,
     arg_expected_checksum: String,
     arg_file_path: String,
     arg_checksum_path: String,
+    arg_directory: String,
+    flag_algo: Option<String>,
+    flag_jobs: String,
+    flag_include: Vec<String>,
+    flag_exclude: Vec<String>,
+}
+
+/// Parse the `--algo` flag, returning an error message for unknown names.
+fn parse_algorithm(flag_algo: &Option<String>) -> Result<Option<Algorithm>, String> {
+    match *flag_algo {
+        None => Ok(None),
+        Some(ref name) => {
+            Algorithm::from_name(name).map(Some).ok_or_else(|| format!("Unknown algorithm: {}", name))
+        }
+    }
+}
+
+/// Parse the `--jobs` flag into a worker count.
+fn parse_jobs(flag_jobs: &str) -> Result<usize, String> {
+    flag_jobs.parse::<usize>().map_err(|e| format!("Invalid --jobs value {:?}: {}", flag_jobs, e))
 }
 
 enum ReturnCode {
@@ -73,11 +108,20 @@ fn get_command(args: &Args) -> Option<fn(&Args) -> u8> {
     match args {
         &Args { cmd_file: true, .. } => Some(file),
         &Args { cmd_manifest: true, .. } => Some(manifest),
+        &Args { cmd_generate: true, .. } => Some(generate),
         _ => None,
     }
 }
 
 fn file(args: &Args) -> u8 {
+    let algorithm = match parse_algorithm(&args.flag_algo) {
+        Ok(algorithm) => algorithm,
+        Err(e) => {
+            println!("Error verifying checksum: {}", e);
+            return ReturnCode::Error as u8;
+        }
+    };
+
     let file_path = canonicalize(&PathBuf::from(&args.arg_file_path));
     if let Err(e) = file_path {
         println!("Error verifying checksum: {:?}", e);
@@ -85,7 +129,7 @@ fn file(args: &Args) -> u8 {
     }
 
     let file_path = file_path.unwrap();
-    match checksum::verify_checksum(&file_path, &args.arg_expected_checksum) {
+    match checksum::verify_checksum(&file_path, &args.arg_expected_checksum, algorithm) {
         Err(e) => {
             println!("Error verifying checksum for  {:?}: {}", file_path, e);
             ReturnCode::Error as u8
@@ -102,7 +146,22 @@ fn file(args: &Args) -> u8 {
 }
 
 fn manifest(args: &Args) -> u8 {
-    let result = checksum::verify_checksums_file(&args.arg_checksum_path);
+    let algorithm = match parse_algorithm(&args.flag_algo) {
+        Ok(algorithm) => algorithm,
+        Err(e) => {
+            println!("Error verifying checksum: {}", e);
+            return ReturnCode::Error as u8;
+        }
+    };
+    let jobs = match parse_jobs(&args.flag_jobs) {
+        Ok(jobs) => jobs,
+        Err(e) => {
+            println!("Error verifying checksum: {}", e);
+            return ReturnCode::Error as u8;
+        }
+    };
+
+    let result = checksum::verify_checksums_file(&args.arg_checksum_path, algorithm, jobs);
     if let Err(e) = result {
         println!("Error verifying checksum: {}", e);
         return ReturnCode::Error as u8;
@@ -138,6 +197,29 @@ fn manifest(args: &Args) -> u8 {
     })
 }
 
+fn generate(args: &Args) -> u8 {
+    let algorithm = match parse_algorithm(&args.flag_algo) {
+        Ok(algorithm) => algorithm.unwrap_or(Algorithm::Sha256),
+        Err(e) => {
+            println!("Error generating manifest: {}", e);
+            return ReturnCode::Error as u8;
+        }
+    };
+
+    match generate::generate_manifest(&args.arg_directory, algorithm, &args.flag_include, &args.flag_exclude) {
+        Err(e) => {
+            println!("Error generating manifest: {}", e);
+            ReturnCode::Error as u8
+        }
+        Ok(lines) => {
+            for line in lines {
+                println!("{}", line);
+            }
+            ReturnCode::Ok as u8
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{file, manifest, Args};
@@ -147,9 +229,15 @@ mod tests {
         let args = Args {
             cmd_file: true,
             cmd_manifest: false,
+            cmd_generate: false,
             arg_expected_checksum: "4d93d51945b88325c213640ef59fc50b".to_string(),
             arg_file_path: "tests/fixtures/foo.txt".to_string(),
             arg_checksum_path: "".to_string(),
+            arg_directory: "".to_string(),
+            flag_algo: None,
+            flag_jobs: "1".to_string(),
+            flag_include: vec![],
+            flag_exclude: vec![],
         };
 
         assert_eq!(file(&args), 0);
@@ -160,9 +248,15 @@ mod tests {
         let args = Args {
             cmd_file: true,
             cmd_manifest: false,
+            cmd_generate: false,
             arg_expected_checksum: "4d93d51945b88325c213640ef59fc50a".to_string(),
             arg_file_path: "tests/fixtures/bar.txt".to_string(),
             arg_checksum_path: "".to_string(),
+            arg_directory: "".to_string(),
+            flag_algo: None,
+            flag_jobs: "1".to_string(),
+            flag_include: vec![],
+            flag_exclude: vec![],
         };
 
         assert_eq!(file(&args), 1);
@@ -173,9 +267,15 @@ mod tests {
         let args = Args {
             cmd_file: true,
             cmd_manifest: false,
+            cmd_generate: false,
             arg_expected_checksum: "ce5188defed222ca612b41580e0d5fe6".to_string(),
             arg_file_path: "tests/fixtures/does-not-exist.csv".to_string(),
             arg_checksum_path: "".to_string(),
+            arg_directory: "".to_string(),
+            flag_algo: None,
+            flag_jobs: "1".to_string(),
+            flag_include: vec![],
+            flag_exclude: vec![],
         };
 
         assert_eq!(file(&args), 2);
@@ -186,9 +286,15 @@ mod tests {
         let args = Args {
             cmd_file: false,
             cmd_manifest: true,
+            cmd_generate: false,
             arg_expected_checksum: "".to_string(),
             arg_file_path: "".to_string(),
             arg_checksum_path: "tests/fixtures/checksum.txt".to_string(),
+            arg_directory: "".to_string(),
+            flag_algo: None,
+            flag_jobs: "1".to_string(),
+            flag_include: vec![],
+            flag_exclude: vec![],
         };
 
         assert_eq!(manifest(&args), 3);